@@ -0,0 +1,46 @@
+use log::Record;
+use log4rs::encode::{Encode, Write};
+use std::fmt;
+use std::io;
+
+/// The closure type wrapped by [`FnEncoder`].
+type EncodeFn = Box<dyn Fn(&mut dyn Write, &Record) -> io::Result<()> + Send + Sync>;
+
+/// An [`Encode`]r backed by a plain closure, for one-off custom formats that
+/// don't warrant a [`PatternEncoder`](log4rs::encode::pattern::PatternEncoder)
+/// string or a hand-written [`Encode`] impl.
+///
+/// Construct via [`FnEncoder::from_display`] for the common case of a
+/// closure that just returns a formatted [`String`], e.g.
+/// `|record| format!("{}:{}", record.level(), record.args())`, or
+/// [`FnEncoder::new`] for a closure that writes directly.
+pub struct FnEncoder {
+    f: EncodeFn,
+}
+
+impl FnEncoder {
+    /// Wrap a closure that writes the encoded record directly to the given writer.
+    pub fn new(
+        f: impl Fn(&mut dyn Write, &Record) -> io::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        Self { f: Box::new(f) }
+    }
+
+    /// Wrap a closure that formats a record as a [`String`].
+    pub fn from_display(f: impl Fn(&Record) -> String + Send + Sync + 'static) -> Self {
+        Self::new(move |w, record| write!(w, "{}", f(record)))
+    }
+}
+
+impl fmt::Debug for FnEncoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FnEncoder").finish_non_exhaustive()
+    }
+}
+
+impl Encode for FnEncoder {
+    fn encode(&self, w: &mut dyn Write, record: &Record) -> anyhow::Result<()> {
+        (self.f)(w, record)?;
+        Ok(())
+    }
+}
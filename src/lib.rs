@@ -15,6 +15,11 @@
 //! * [`init_logging_once_for`](test_logging::init_logging_once_for), which
 //!   does the same, but automatically creates a sensible config for the given
 //!   targets.
+//! * [`init_logging_once_from_directives`](test_logging::init_logging_once_from_directives),
+//!   which does the same, but takes a single `env_logger`-style filter
+//!   directive string to configure per-target verbosity.
+//! * [`FnEncoder`](test_logging::FnEncoder), an [`Encode`](log4rs::encode::Encode)
+//!   adapter that wraps a closure, for one-off formats at the call site.
 //!
 //! # Testing your logs
 //! If you want to test your logs, i.e. write tests that make assertions about
@@ -27,6 +32,19 @@
 //! * [`logging_test_setup_mock`](log_testing::logging_test_setup_mock) which does
 //!   the same, but automatically creates a [`MockAppender`](log_testing::MockAppender)
 //!   for you to save even more effort.
+//! * [`logging_test_setup_structured`](log_testing::logging_test_setup_structured),
+//!   which captures structured [`CapturedRecord`](log_testing::CapturedRecord)s
+//!   instead of formatted strings, so assertions don't need to parse text.
+//! * [`capture_logs_local`](log_testing::capture_logs_local), which captures
+//!   logs into a thread-local buffer instead of serializing tests behind a
+//!   shared mutex, allowing logging tests to run in parallel.
+//! * [`expect_logs`](log_testing::expect_logs), which returns an
+//!   [`ExpectGuard`](log_testing::ExpectGuard) so a test can declare log
+//!   expectations (`assert_logged`, `assert_logged_regex`, `assert_no_logs_matching`)
+//!   instead of manually filtering a buffer.
+//! * [`logging_test_setup_mock_with`](log_testing::logging_test_setup_mock_with),
+//!   which accepts a closure directly instead of a preconfigured
+//!   [`Encode`](log4rs::encode::Encode)r, via [`FnEncoder`](log_testing::FnEncoder).
 //!
 //! # Features
 //! The two halves of this module are feature-gated, so you can disable anything
@@ -37,6 +55,7 @@
 //! | `log_testing`  | the [`log_testing`] module  |
 //! | `test_logging` | the [`test_logging`] module |
 
+mod fn_encoder;
 mod string_buffer;
 
 /// Requires the `log_testing` feature.
@@ -25,35 +25,100 @@
 //! ```
 
 use lazy_static::lazy_static;
-use log::{LevelFilter, Record};
+use log::{Level, LevelFilter, Record};
 use log4rs::{
     append::Append,
     config::{Appender, Root},
     encode::{pattern::PatternEncoder, Encode},
     Config, Handle,
 };
+use regex::Regex;
+use std::cell::RefCell;
 use std::sync::{Arc, Mutex, MutexGuard};
 
 use crate::string_buffer::StringBuffer;
 
+pub use crate::fn_encoder::FnEncoder;
+
 /// A thread-safe handle to the list of log messages written by a [`MockAppender`].
 pub type LogsHandle = Arc<Mutex<Vec<String>>>;
 
+/// A single structured log event, captured by a [`StructuredMockAppender`]
+/// without going through an [`Encode`]r.
+///
+/// This avoids the brittle substring matching that [`LogsHandle`] invites,
+/// e.g. `s.contains("INFO")`, in favour of comparing fields directly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedRecord {
+    pub level: Level,
+    pub target: String,
+    pub module_path: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+    pub kvs: Vec<(String, String)>,
+}
+
+impl CapturedRecord {
+    fn from_record(record: &Record) -> Self {
+        let mut kvs = Vec::new();
+        let mut visitor = KeyValueVisitor(&mut kvs);
+        // The `log` crate's structured key-value API can only fail if a
+        // visitor returns an error, which ours never does.
+        record.key_values().visit(&mut visitor).unwrap();
+        Self {
+            level: record.level(),
+            target: record.target().to_string(),
+            module_path: record.module_path().map(str::to_string),
+            file: record.file().map(str::to_string),
+            line: record.line(),
+            message: record.args().to_string(),
+            kvs,
+        }
+    }
+}
+
+/// A thread-safe handle to the list of structured records captured by a
+/// [`StructuredMockAppender`].
+pub type RecordsHandle = Arc<Mutex<Vec<CapturedRecord>>>;
+
+/// A [`log::kv::VisitSource`] that renders every value with its [`Display`](std::fmt::Display)
+/// impl and collects the results in order.
+struct KeyValueVisitor<'a>(&'a mut Vec<(String, String)>);
+
+impl<'a, 'kvs> log::kv::VisitSource<'kvs> for KeyValueVisitor<'a> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+}
+
 /// A mock appender that encodes its messages to a [`Vec<String>`].
 #[derive(Debug)]
 pub struct MockAppender {
     logs: LogsHandle,
     encoder: Box<dyn Encode>,
+    level: LevelFilter,
 }
 
 impl MockAppender {
-    /// Create a new [`MockAppender`], returning it along with a handle to its
-    /// log buffer.
-    pub fn new(encoder: Box<dyn Encode>) -> (Self, LogsHandle) {
+    /// Create a new [`MockAppender`] at the given level, returning it along
+    /// with a handle to its log buffer.
+    ///
+    /// The level is enforced by the appender itself rather than by the
+    /// [`Config`]'s root logger, since the config given to
+    /// [`logging_test_setup`] should keep its root at
+    /// [`LevelFilter::Trace`] - see its docs for why.
+    pub fn new(encoder: Box<dyn Encode>, level: LevelFilter) -> (Self, LogsHandle) {
         let logs: LogsHandle = Default::default();
         let appender = Self {
             logs: logs.clone(),
             encoder,
+            level,
         };
         (appender, logs)
     }
@@ -61,6 +126,12 @@ impl MockAppender {
 
 impl Append for MockAppender {
     fn append(&self, record: &Record) -> anyhow::Result<()> {
+        if route_to_local_capture(record) {
+            return Ok(());
+        }
+        if record.level() > self.level {
+            return Ok(());
+        }
         let mut log_line = StringBuffer::new();
         self.encoder.encode(&mut log_line, record).unwrap();
         self.logs.lock().unwrap().push(log_line.0);
@@ -72,12 +143,77 @@ impl Append for MockAppender {
     }
 }
 
+/// A mock appender that captures each [`Record`] as a [`CapturedRecord`],
+/// bypassing encoding entirely so tests can assert on structured fields
+/// (including key-values) instead of parsing formatted text.
+#[derive(Debug)]
+pub struct StructuredMockAppender {
+    records: RecordsHandle,
+    level: LevelFilter,
+}
+
+impl StructuredMockAppender {
+    /// Create a new [`StructuredMockAppender`] at the given level, returning
+    /// it along with a handle to its record buffer.
+    ///
+    /// The level is enforced by the appender itself rather than by the
+    /// [`Config`]'s root logger, since the config given to
+    /// [`logging_test_setup`] should keep its root at
+    /// [`LevelFilter::Trace`] - see its docs for why.
+    pub fn new(level: LevelFilter) -> (Self, RecordsHandle) {
+        let records: RecordsHandle = Default::default();
+        let appender = Self {
+            records: records.clone(),
+            level,
+        };
+        (appender, records)
+    }
+}
+
+impl Append for StructuredMockAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        if route_to_local_capture(record) {
+            return Ok(());
+        }
+        if record.level() > self.level {
+            return Ok(());
+        }
+        self.records
+            .lock()
+            .unwrap()
+            .push(CapturedRecord::from_record(record));
+        Ok(())
+    }
+
+    fn flush(&self) {
+        // no-op
+    }
+}
+
 lazy_static! {
     /// A handle to the global logger that will be created on first access.
     /// Can be used to set and re-set the config.
+    ///
+    /// This is the *only* place in this module that calls [`log4rs::init_config`];
+    /// there can only ever be one global logger, so every entry point that
+    /// needs one - [`logging_test_setup`] and [`capture_logs_local`] alike -
+    /// goes through this same lazily-initialized handle rather than each
+    /// racing to install their own. The default config below, installed on
+    /// first access, routes everything through [`ParallelCaptureAppender`]
+    /// so that [`capture_logs_local`] works even before any
+    /// `logging_test_setup*` call has run; [`logging_test_setup`] is then
+    /// free to swap in a different config via [`Handle::set_config`] as
+    /// before, since [`MockAppender`] and [`StructuredMockAppender`] check
+    /// the calling thread's local capture buffer before falling back to
+    /// their own.
     static ref HANDLE: Handle = {
-        let root = Root::builder().build(LevelFilter::Off);
-        let config = Config::builder().build(root).unwrap();
+        const APPENDER_NAME: &str = "parallel_capture";
+        let appender =
+            Appender::builder().build(APPENDER_NAME, Box::<ParallelCaptureAppender>::default());
+        let root = Root::builder()
+            .appender(APPENDER_NAME)
+            .build(LevelFilter::Trace);
+        let config = Config::builder().appender(appender).build(root).unwrap();
         log4rs::init_config(config).unwrap()
     };
 }
@@ -85,7 +221,8 @@ lazy_static! {
 /// A mutex for ensuring tests execute sequentially.
 /// Unfortunately there is no safe way to parallelize logging tests thanks to
 /// the global logger and the fact that the target is chosen by the code doing
-/// the logging.
+/// the logging, *unless* they opt into [`capture_logs_local`] instead, which
+/// routes records through a thread-local buffer rather than a swappable config.
 static TEST_MUTEX: Mutex<()> = Mutex::new(());
 
 /// Call this at the start of a logging test to configure the logger.
@@ -93,6 +230,18 @@ static TEST_MUTEX: Mutex<()> = Mutex::new(());
 /// The returned mutex guard ensures no other logging test can execute
 /// simultaneously; this is vital for correctness since there is only one
 /// global logger. Do not drop it until the end of the test.
+///
+/// `config`'s root logger should always be built at [`LevelFilter::Trace`],
+/// with any desired verbosity enforced by the appender instead (as
+/// [`logging_test_setup_mock`] and [`logging_test_setup_structured`] do).
+/// [`Handle::set_config`] calls [`log::set_max_level`] on the *global*,
+/// process-wide max level to match the root logger it's given - if some
+/// other, concurrently-running test's [`capture_logs_local`] thread needed a
+/// more verbose level than this `config`'s root, a lower root here would
+/// silently starve it of records regardless of what level it asked for.
+/// Pinning the root (and therefore the global max level) at `Trace` here and
+/// filtering in the appender instead keeps every thread's logging intact no
+/// matter which `config` happens to be active when.
 pub fn logging_test_setup(config: Config) -> MutexGuard<'static, ()> {
     let guard = TEST_MUTEX.lock();
     HANDLE.set_config(config);
@@ -119,19 +268,236 @@ pub fn logging_test_setup_mock(
     let encoder = encoder
         .into()
         .unwrap_or_else(|| Box::new(PatternEncoder::new("{l} {t} {m}")));
-    let (mock, logs) = MockAppender::new(encoder);
-    let appender = Appender::builder().build(APPENDER_NAME, Box::new(mock));
     let level = level.into().unwrap_or(LevelFilter::Trace);
-    let root = Root::builder().appender(APPENDER_NAME).build(level);
+    let (mock, logs) = MockAppender::new(encoder, level);
+    let appender = Appender::builder().build(APPENDER_NAME, Box::new(mock));
+    // The root stays at `Trace` regardless of `level`; see `logging_test_setup`.
+    let root = Root::builder()
+        .appender(APPENDER_NAME)
+        .build(LevelFilter::Trace);
     let config = Config::builder().appender(appender).build(root).unwrap();
     (logging_test_setup(config), logs)
 }
 
+/// A convenient wrapper for [`logging_test_setup_mock`] that accepts a
+/// closure directly, rather than a preconfigured [`Encode`]r, via
+/// [`FnEncoder::from_display`].
+///
+/// Defaults:
+/// * `level = LevelFilter::Trace`
+pub fn logging_test_setup_mock_with(
+    level: impl Into<Option<LevelFilter>>,
+    f: impl Fn(&Record) -> String + Send + Sync + 'static,
+) -> (MutexGuard<'static, ()>, LogsHandle) {
+    let encoder: Box<dyn Encode> = Box::new(FnEncoder::from_display(f));
+    logging_test_setup_mock(level, encoder)
+}
+
+/// A convenient wrapper for [`logging_test_setup`] that configures the global
+/// logger with a fresh [`StructuredMockAppender`].
+///
+/// Defaults:
+/// * `level = LevelFilter::Trace`
+pub fn logging_test_setup_structured(
+    level: impl Into<Option<LevelFilter>>,
+) -> (MutexGuard<'static, ()>, RecordsHandle) {
+    const APPENDER_NAME: &str = "structured_mock";
+    let level = level.into().unwrap_or(LevelFilter::Trace);
+    let (mock, records) = StructuredMockAppender::new(level);
+    let appender = Appender::builder().build(APPENDER_NAME, Box::new(mock));
+    // The root stays at `Trace` regardless of `level`; see `logging_test_setup`.
+    let root = Root::builder()
+        .appender(APPENDER_NAME)
+        .build(LevelFilter::Trace);
+    let config = Config::builder().appender(appender).build(root).unwrap();
+    (logging_test_setup(config), records)
+}
+
+thread_local! {
+    /// The current thread's capture buffer, if any, and the level it was
+    /// requested at. Set by [`capture_logs_local`], cleared when the
+    /// returned [`ParallelCaptureGuard`] is dropped.
+    static LOCAL_CAPTURE: RefCell<Option<(LevelFilter, RecordsHandle)>> = const { RefCell::new(None) };
+}
+
+/// If the calling thread has registered a capture buffer via
+/// [`capture_logs_local`], and `record`'s level satisfies the level it was
+/// requested at, push it there. Returns whether the calling thread had a
+/// buffer registered at all, regardless of whether the record passed its
+/// level filter.
+///
+/// [`MockAppender`], [`StructuredMockAppender`] and [`ParallelCaptureAppender`]
+/// all call this first and skip their own logic when it returns `true`, so
+/// that a thread capturing locally is served correctly no matter which of
+/// them happens to be the currently-configured appender.
+fn route_to_local_capture(record: &Record) -> bool {
+    LOCAL_CAPTURE.with(|cell| match cell.borrow().as_ref() {
+        Some((level, records)) => {
+            if record.level() <= *level {
+                records.lock().unwrap().push(CapturedRecord::from_record(record));
+            }
+            true
+        }
+        None => false,
+    })
+}
+
+/// [`HANDLE`]'s default appender, installed the first time the global logger
+/// is accessed by any function in this module. It does nothing but call
+/// [`route_to_local_capture`], so that [`capture_logs_local`] works even
+/// before any `logging_test_setup*` call has installed a different config.
+#[derive(Debug, Default)]
+struct ParallelCaptureAppender;
+
+impl Append for ParallelCaptureAppender {
+    fn append(&self, record: &Record) -> anyhow::Result<()> {
+        route_to_local_capture(record);
+        Ok(())
+    }
+
+    fn flush(&self) {
+        // no-op
+    }
+}
+
+/// A guard returned by [`capture_logs_local`] that scopes log capture to the
+/// current thread for its lifetime.
+///
+/// While held, [`Record`]s logged *from the thread that created it* are
+/// pushed into the associated [`RecordsHandle`]. Dropping the guard clears
+/// the thread-local buffer, so any further records on this thread are dropped
+/// until another guard is created.
+#[derive(Debug)]
+pub struct ParallelCaptureGuard {
+    _private: (),
+}
+
+impl Drop for ParallelCaptureGuard {
+    fn drop(&mut self) {
+        LOCAL_CAPTURE.with(|cell| *cell.borrow_mut() = None);
+    }
+}
+
+/// Capture logs emitted on the current thread into a fresh [`RecordsHandle`],
+/// without taking any lock shared with other tests. This allows logging tests
+/// to run in parallel, each on its own harness thread.
+///
+/// Defaults:
+/// * `level = LevelFilter::Trace`
+///
+/// # Limitations
+/// Only records logged directly on the thread that calls this function are
+/// captured. Logs emitted from threads *spawned by* the test do not inherit
+/// the thread-local buffer and will not be captured - the same limitation
+/// other capture frameworks (e.g. Rust's own `libtest` output capture) have.
+pub fn capture_logs_local(
+    level: impl Into<Option<LevelFilter>>,
+) -> (ParallelCaptureGuard, RecordsHandle) {
+    // Force the global logger to be installed with `HANDLE`'s default config
+    // if nothing has installed one yet; a no-op if some earlier call to this
+    // function or to `logging_test_setup` already did so.
+    lazy_static::initialize(&HANDLE);
+    let level = level.into().unwrap_or(LevelFilter::Trace);
+    let records: RecordsHandle = Default::default();
+    LOCAL_CAPTURE.with(|cell| *cell.borrow_mut() = Some((level, records.clone())));
+    (ParallelCaptureGuard { _private: () }, records)
+}
+
+/// A guard returned by [`expect_logs`] that lets a test declare expectations
+/// about log output as it happens, rather than inspecting a buffer
+/// afterwards.
+///
+/// Records are captured into an internal buffer as usual; [`assert_logged`](Self::assert_logged)
+/// and [`assert_logged_regex`](Self::assert_logged_regex) pop the first
+/// matching record out of that buffer, so each record can only satisfy one
+/// assertion. Call [`strict`](Self::strict) to additionally panic on `Drop`
+/// if any records are left unasserted, catching unexpected log output.
+pub struct ExpectGuard {
+    _test_guard: MutexGuard<'static, ()>,
+    records: RecordsHandle,
+    strict: bool,
+}
+
+impl ExpectGuard {
+    /// Enable strict mode: on `Drop`, panic if any captured records have not
+    /// been consumed by [`assert_logged`](Self::assert_logged) or
+    /// [`assert_logged_regex`](Self::assert_logged_regex).
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Pop and return the first captured record matching `predicate`,
+    /// panicking with the remaining buffer contents if none match.
+    pub fn assert_logged(&self, predicate: impl Fn(&CapturedRecord) -> bool) -> CapturedRecord {
+        let mut records = self.records.lock().unwrap();
+        match records.iter().position(predicate) {
+            Some(i) => records.remove(i),
+            None => panic!(
+                "expected a log record matching the given predicate, but none was found.\n\
+                 Remaining unasserted records: {:#?}",
+                *records
+            ),
+        }
+    }
+
+    /// Pop and return the first captured record at the given `level` whose
+    /// message matches the regex `pattern`, panicking if none match.
+    pub fn assert_logged_regex(&self, level: Level, pattern: &str) -> CapturedRecord {
+        let re =
+            Regex::new(pattern).unwrap_or_else(|e| panic!("invalid regex \"{pattern}\": {e}"));
+        self.assert_logged(|record| record.level == level && re.is_match(&record.message))
+    }
+
+    /// Assert that no captured record matches `predicate`, without consuming
+    /// anything from the buffer.
+    pub fn assert_no_logs_matching(&self, predicate: impl Fn(&CapturedRecord) -> bool) {
+        let records = self.records.lock().unwrap();
+        if let Some(record) = records.iter().find(|record| predicate(record)) {
+            panic!("expected no matching log records, but found: {record:#?}");
+        }
+    }
+}
+
+impl Drop for ExpectGuard {
+    fn drop(&mut self) {
+        // Don't double-panic if the test is already failing for another reason.
+        if self.strict && !std::thread::panicking() {
+            let records = self.records.lock().unwrap();
+            if !records.is_empty() {
+                panic!(
+                    "strict ExpectGuard dropped with {} un-asserted record(s): {:#?}",
+                    records.len(),
+                    *records
+                );
+            }
+        }
+    }
+}
+
+/// Start a scope of log expectations. Like [`logging_test_setup_structured`],
+/// this configures the global logger with a fresh capture buffer, but wraps
+/// it in an [`ExpectGuard`] so a test can declare expectations about what
+/// gets logged instead of inspecting the buffer afterwards.
+///
+/// Defaults:
+/// * `level = LevelFilter::Trace`
+pub fn expect_logs(level: impl Into<Option<LevelFilter>>) -> ExpectGuard {
+    let (test_guard, records) = logging_test_setup_structured(level);
+    ExpectGuard {
+        _test_guard: test_guard,
+        records,
+        strict: false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use log::{error, info, warn};
+    use std::sync::Barrier;
+    use std::time::Duration;
 
     #[test]
     fn simple_mock_example() {
@@ -164,4 +530,113 @@ mod tests {
         assert_eq!(logs.len(), 2);
         assert_eq!(logs[0], "this will appear");
     }
+
+    #[test]
+    fn closure_encoder_example() {
+        let (_guard, logs_handle) =
+            logging_test_setup_mock_with(None, |record| format!("{}:{}", record.level(), record.args()));
+
+        warn!("uh oh");
+
+        let logs = logs_handle.lock().unwrap();
+        assert_eq!(logs[0], "WARN:uh oh");
+    }
+
+    #[test]
+    fn structured_mock_example() {
+        let (_guard, records_handle) = logging_test_setup_structured(None);
+
+        error!(user = 42; "Oh, no!");
+
+        let logs = records_handle.lock().unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].level, Level::Error);
+        assert_eq!(logs[0].message, "Oh, no!");
+        assert_eq!(logs[0].kvs, vec![("user".to_string(), "42".to_string())]);
+    }
+
+    #[test]
+    fn parallel_capture_example() {
+        // Deliberately doesn't take `TEST_MUTEX`, so this runs concurrently
+        // with the other, mutex-serialized tests in this module - exercising
+        // `capture_logs_local` alongside whichever `logging_test_setup*`
+        // config happens to be globally active at the time.
+        let (_guard, records) = capture_logs_local(LevelFilter::Info);
+
+        info!("captured on this thread");
+
+        let logs = records.lock().unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].level, Level::Info);
+        assert_eq!(logs[0].message, "captured on this thread");
+    }
+
+    #[test]
+    fn concurrent_capture_not_starved_by_other_test_level() {
+        // Regression test for a previous bug: `logging_test_setup`'s config
+        // used to build its root logger at the caller's requested `level`,
+        // which `Handle::set_config` then installed as the *global* max log
+        // level - so a concurrently-running `Warn`-level test here would
+        // silently drop this test's `Trace`-level `capture_logs_local`
+        // records before they were even constructed. The main thread
+        // deliberately doesn't take `TEST_MUTEX`; the spawned thread stands in
+        // for a concurrently-running, mutex-serialized `Warn`-level test by
+        // holding `TEST_MUTEX` and its config while the main thread logs.
+        let barrier = Arc::new(Barrier::new(2));
+        let other_barrier = barrier.clone();
+        let other_thread = std::thread::spawn(move || {
+            let (_guard, _logs) = logging_test_setup_mock(LevelFilter::Warn, None);
+            other_barrier.wait();
+            // Keep the low-verbosity config active while the main thread logs.
+            std::thread::sleep(Duration::from_millis(50));
+        });
+
+        barrier.wait();
+        let (_guard, records) = capture_logs_local(LevelFilter::Trace);
+        info!("captured despite a concurrent Warn-level test");
+
+        let logs = records.lock().unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(
+            logs[0].message,
+            "captured despite a concurrent Warn-level test"
+        );
+        drop(logs);
+
+        other_thread.join().unwrap();
+    }
+
+    #[test]
+    fn expect_logs_example() {
+        let expect = expect_logs(LevelFilter::Info).strict();
+
+        info!("Hello, world!");
+        error!(user = 42; "Oh, no!");
+
+        expect.assert_logged_regex(Level::Info, "^Hello");
+        expect.assert_logged(|record| {
+            record.level == Level::Error
+                && record.kvs == vec![("user".to_string(), "42".to_string())]
+        });
+    }
+
+    #[test]
+    fn assert_no_logs_matching_example() {
+        let expect = expect_logs(LevelFilter::Info);
+
+        info!("all good");
+
+        expect.assert_no_logs_matching(|record| record.level == Level::Error);
+        expect.assert_logged(|record| record.message == "all good");
+    }
+
+    #[test]
+    #[should_panic(expected = "un-asserted")]
+    fn strict_guard_panics_on_leftover_logs() {
+        let expect = expect_logs(LevelFilter::Info).strict();
+
+        info!("nobody checked this one");
+
+        drop(expect);
+    }
 }
@@ -26,10 +26,13 @@ use log4rs::{
     encode::{pattern::PatternEncoder, Encode},
 };
 use std::io::{self, Write};
+use std::str::FromStr;
 use std::sync::Once;
 
 use crate::string_buffer::StringBuffer;
 
+pub use crate::fn_encoder::FnEncoder;
+
 /// An appender that uses [`print!`] internally. This is less performant than
 /// a normal `ConsoleAppender`, but ensures output gets captured by the
 /// standard test harness.
@@ -44,6 +47,12 @@ impl TestConsoleAppender {
         Self { encoder }
     }
 
+    /// Create a new [`TestConsoleAppender`] from a closure, rather than a
+    /// preconfigured [`Encode`]r, via [`FnEncoder::from_display`].
+    pub fn new_with(f: impl Fn(&Record) -> String + Send + Sync + 'static) -> Self {
+        Self::new(Box::new(FnEncoder::from_display(f)))
+    }
+
     /// Construct a sensible [`Config`] using a [`TestConsoleAppender`] and [`PatternEncoder`].
     ///
     /// If `targets` is empty, the root logger will be enabled at the given `level`.
@@ -91,6 +100,81 @@ impl TestConsoleAppender {
                 .unwrap()
         }
     }
+
+    /// Construct a [`Config`] from an `env_logger`-style filter directive
+    /// string, allowing different targets to be configured with different
+    /// verbosity from a single spec, e.g. `"info,foo=debug,foo::bar=off"`.
+    ///
+    /// See [`parse_directives`] for the accepted syntax.
+    ///
+    /// Defaults:
+    /// * `pattern = "{l} {M}::{L} {m}{n}"`
+    pub fn make_config_from_directives<'b>(
+        spec: &str,
+        pattern: impl Into<Option<&'b str>>,
+    ) -> Config {
+        let (root_level, targets) = parse_directives(spec);
+        let pattern = pattern.into().unwrap_or("{l} {M}::{L} {m}{n}");
+        const APPENDER_NAME: &str = "appender";
+
+        // Create encoder and appender.
+        let encoder = Box::new(PatternEncoder::new(pattern));
+        let console = Box::new(TestConsoleAppender::new(encoder));
+        let appender = Appender::builder().build(APPENDER_NAME, console);
+
+        // Create a logger for each directive target.
+        let loggers = targets
+            .into_iter()
+            .map(|(target, level)| Logger::builder().appender(APPENDER_NAME).build(target, level))
+            .collect::<Vec<_>>();
+
+        // Create the root logger and final config.
+        let root = Root::builder().appender(APPENDER_NAME).build(root_level);
+        Config::builder()
+            .appender(appender)
+            .loggers(loggers)
+            .build(root)
+            .unwrap()
+    }
+}
+
+/// Parse an `env_logger`-style filter directive string, e.g.
+/// `"info,foo=debug,foo::bar=off,baz=trace"`.
+///
+/// The string is split on commas, and each segment trimmed of whitespace.
+/// A segment without an `=` sets the root level (if given more than once,
+/// the last one wins); every other segment must be of the form
+/// `target=level` and becomes a per-target override. Levels are parsed
+/// case-insensitively via [`LevelFilter::from_str`].
+///
+/// The root level defaults to [`LevelFilter::Off`] if no bare level is given.
+/// Duplicate targets and unparsable levels are not allowed and will panic.
+pub fn parse_directives(spec: &str) -> (LevelFilter, Vec<(String, LevelFilter)>) {
+    let mut root = LevelFilter::Off;
+    let mut targets: Vec<(String, LevelFilter)> = Vec::new();
+    for segment in spec.split(',') {
+        let segment = segment.trim();
+        if segment.is_empty() {
+            continue;
+        }
+        match segment.split_once('=') {
+            None => {
+                root = LevelFilter::from_str(segment)
+                    .unwrap_or_else(|_| panic!("invalid level in directive: \"{segment}\""));
+            }
+            Some((target, level)) => {
+                let target = target.trim();
+                let level = level.trim();
+                let level = LevelFilter::from_str(level)
+                    .unwrap_or_else(|_| panic!("invalid level in directive: \"{segment}\""));
+                if targets.iter().any(|(t, _)| t == target) {
+                    panic!("duplicate target in directive: \"{target}\"");
+                }
+                targets.push((target.to_string(), level));
+            }
+        }
+    }
+    (root, targets)
 }
 
 impl Append for TestConsoleAppender {
@@ -142,3 +226,76 @@ pub fn init_logging_once_for<'a, 'b>(
     let config = TestConsoleAppender::make_config(targets, level, pattern);
     init_logging_once(config);
 }
+
+/// A convenient wrapper for [`TestConsoleAppender::make_config_from_directives`]
+/// and [`init_logging_once`], which initializes logging once with a per-target
+/// config built from a single `env_logger`-style directive string.
+///
+/// See [`parse_directives`] for the accepted syntax.
+pub fn init_logging_once_from_directives<'b>(spec: &str, pattern: impl Into<Option<&'b str>>) {
+    // No need to bother even constructing the config if we know init is already done.
+    if INIT.is_completed() {
+        return;
+    }
+    let config = TestConsoleAppender::make_config_from_directives(spec, pattern);
+    init_logging_once(config);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bare_level_sets_root() {
+        let (root, targets) = parse_directives("info");
+        assert_eq!(root, LevelFilter::Info);
+        assert!(targets.is_empty());
+    }
+
+    #[test]
+    fn parse_mixed_directives() {
+        let (root, targets) = parse_directives("info,foo=debug,foo::bar=off,baz=trace");
+        assert_eq!(root, LevelFilter::Info);
+        assert_eq!(
+            targets,
+            vec![
+                ("foo".to_string(), LevelFilter::Debug),
+                ("foo::bar".to_string(), LevelFilter::Off),
+                ("baz".to_string(), LevelFilter::Trace),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_trims_whitespace_around_target_and_level() {
+        let (root, targets) = parse_directives("info, foo = debug");
+        assert_eq!(root, LevelFilter::Info);
+        assert_eq!(targets, vec![("foo".to_string(), LevelFilter::Debug)]);
+    }
+
+    #[test]
+    fn parse_is_case_insensitive() {
+        let (root, targets) = parse_directives("INFO,foo=DEBUG");
+        assert_eq!(root, LevelFilter::Info);
+        assert_eq!(targets, vec![("foo".to_string(), LevelFilter::Debug)]);
+    }
+
+    #[test]
+    fn parse_last_bare_level_wins() {
+        let (root, targets) = parse_directives("info,debug");
+        assert_eq!(root, LevelFilter::Debug);
+        assert!(targets.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid level")]
+    fn parse_panics_on_bad_level() {
+        parse_directives("info,foo=nonsense");
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate target")]
+    fn parse_panics_on_duplicate_target() {
+        parse_directives("foo=debug,foo=trace");
+    }
+}